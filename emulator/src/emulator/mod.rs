@@ -0,0 +1,64 @@
+//! The emulator handle and its per-instance state.
+//!
+//! `Inner<T>` holds everything `syscall_handler` needs to thread across
+//! dispatches: the pre-existing svc-call table plus the syscall override
+//! registry, seccomp filter chain, mmap range allocator, syscall tracer, and
+//! SCM_RIGHTS mailboxes. `AndroidEmulator` is a cheap `Rc`-backed handle onto
+//! it, clonable into the closures the backends install their hooks with.
+
+use std::cell::UnsafeCell;
+use std::rc::Rc;
+
+use crate::backend::Backend;
+
+pub mod thread;
+mod syscall_handler;
+
+pub use crate::memory::svc_memory::SvcMemory;
+
+/// Opaque handle to a guest virtual-memory address; the rest of its API
+/// lives with the memory subsystem and isn't needed by anything in this
+/// crate's syscall dispatch path.
+pub struct VMPointer;
+
+pub(crate) struct Inner<T> {
+    pub(crate) svc_memory: SvcMemory,
+    pub(crate) syscall_registry: syscall_handler::SyscallRegistry<T>,
+    pub(crate) seccomp_filters: Vec<Rc<Vec<syscall_handler::SockFilter>>>,
+    pub(crate) range_allocator: syscall_handler::RangeAllocator,
+    pub(crate) syscall_tracer: syscall_handler::SyscallTracer<T>,
+    pub(crate) scm_rights: syscall_handler::ScmRightsState,
+}
+
+/// A cloneable handle onto one running emulator instance and its backend.
+pub struct AndroidEmulator<'a, T> {
+    pub backend: Backend<'a, T>,
+    inner: Rc<UnsafeCell<Inner<T>>>,
+}
+
+impl<'a, T: Clone> AndroidEmulator<'a, T> {
+    pub(crate) fn inner_mut(&self) -> &mut Inner<T> {
+        unsafe { &mut *self.inner.get() }
+    }
+
+    /// Stop emulation, matching the `Backend::emu_stop` the dynarmic/unicorn
+    /// dispatch paths call directly when they already hold a backend
+    /// reference.
+    pub fn emu_stop(&self, status: thread::TaskStatus) -> Result<(), String> {
+        self.backend.emu_stop(status, self)
+            .map(|_| ())
+            .map_err(|e| format!("{:?}", e))
+    }
+}
+
+impl<'a, T: Clone> AndroidEmulator<'a, T>
+where
+    Backend<'a, T>: Clone,
+{
+    pub fn clone(&self) -> AndroidEmulator<'a, T> {
+        AndroidEmulator {
+            backend: self.backend.clone(),
+            inner: Rc::clone(&self.inner),
+        }
+    }
+}
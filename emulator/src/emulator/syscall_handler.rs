@@ -23,6 +23,50 @@ pub const PRE_CALLBACK_SYSCALL_NUMBER: u64 = 0x8888 - 1;
 
 const SWI_MAX: i32 = 0xffff;
 
+/// errno returned in X0 (as a negative value) when a syscall has no handler
+/// and the emulator's [`UnsupportedSyscallPolicy`] is [`UnsupportedSyscallPolicy::Enosys`].
+const ENOSYS: i64 = 38;
+
+/// A user-supplied stand-in for a syscall, installed via
+/// [`AndroidEmulator::register_syscall_override`]. It is handed the same
+/// `(backend, emulator)` pair as the crate's built-in handlers and is
+/// responsible for reading its own arguments and writing X0 if it wants to
+/// return a value.
+pub type SyscallOverride<T> = Rc<dyn Fn(&Backend<T>, &AndroidEmulator<T>)>;
+
+/// What to do with a [`Syscalls`] number that has neither a built-in handler
+/// nor a registered override.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum UnsupportedSyscallPolicy {
+    /// Abort the emulator, as `syscall()` always used to. Useful while
+    /// developing against a target so gaps in coverage are loud.
+    Panic,
+    /// Write `-ENOSYS` into X0 and let the guest keep running. Matches what
+    /// a real kernel does for a syscall number it doesn't implement, so
+    /// targets that merely probe for optional syscalls keep working.
+    Enosys,
+}
+
+impl Default for UnsupportedSyscallPolicy {
+    fn default() -> Self {
+        UnsupportedSyscallPolicy::Panic
+    }
+}
+
+/// Per-emulator table of syscall overrides plus the fallback policy applied
+/// when a number is in neither the table nor the built-in match in `syscall()`.
+#[derive(Default)]
+pub(crate) struct SyscallRegistry<T> {
+    overrides: HashMap<u64, SyscallOverride<T>>,
+    unsupported_policy: UnsupportedSyscallPolicy,
+}
+
+impl<T> SyscallRegistry<T> {
+    fn get(&self, nr: Syscalls) -> Option<&SyscallOverride<T>> {
+        self.overrides.get(&(nr as u64))
+    }
+}
+
 #[inline]
 #[cfg(feature = "unicorn_backend")]
 fn arm64_syscall_handler_unicorn<T: Clone>(unicorn: &unicorn_engine::Unicorn<T>, intno: u32, swi: i32, emulator: &AndroidEmulator<T>) {
@@ -165,11 +209,713 @@ fn arm64_syscall_handler_dynarmic<T: Clone>(swi: i32, emulator: &AndroidEmulator
     }
 }
 
+/// AUDIT_ARCH_AARCH64, stamped into the synthesized `seccomp_data` record.
+const AUDIT_ARCH_AARCH64: u32 = 0xC000_00B7;
+
+const PR_SET_SECCOMP: u64 = 22;
+const SECCOMP_MODE_FILTER: u64 = 2;
+
+// cBPF instruction classes and field masks, from linux/filter.h.
+const BPF_CLASS_MASK: u16 = 0x07;
+const BPF_LD: u16 = 0x00;
+const BPF_LDX: u16 = 0x01;
+const BPF_ST: u16 = 0x02;
+const BPF_STX: u16 = 0x03;
+const BPF_ALU: u16 = 0x04;
+const BPF_JMP: u16 = 0x05;
+const BPF_RET: u16 = 0x06;
+
+const BPF_MODE_MASK: u16 = 0xe0;
+const BPF_IMM: u16 = 0x00;
+const BPF_ABS: u16 = 0x20;
+const BPF_MEM: u16 = 0x60;
+
+const BPF_OP_MASK: u16 = 0xf0;
+const BPF_ADD: u16 = 0x00;
+const BPF_SUB: u16 = 0x10;
+const BPF_MUL: u16 = 0x20;
+const BPF_DIV: u16 = 0x30;
+const BPF_OR: u16 = 0x40;
+const BPF_AND: u16 = 0x50;
+const BPF_LSH: u16 = 0x60;
+const BPF_RSH: u16 = 0x70;
+const BPF_NEG: u16 = 0x80;
+const BPF_MOD: u16 = 0x90;
+const BPF_XOR: u16 = 0xa0;
+const BPF_JA: u16 = 0x00;
+const BPF_JEQ: u16 = 0x10;
+const BPF_JGT: u16 = 0x20;
+const BPF_JGE: u16 = 0x30;
+const BPF_JSET: u16 = 0x40;
+
+const BPF_SRC_MASK: u16 = 0x08;
+const BPF_X: u16 = 0x08;
+
+// SECCOMP_RET_* action bits, from uapi/linux/seccomp.h.
+const SECCOMP_RET_ACTION_FULL: u32 = 0xffff_0000;
+const SECCOMP_RET_DATA: u32 = 0x0000_ffff;
+const SECCOMP_RET_KILL_THREAD: u32 = 0x0000_0000;
+const SECCOMP_RET_TRAP: u32 = 0x0003_0000;
+const SECCOMP_RET_ERRNO: u32 = 0x0005_0000;
+const SECCOMP_RET_ALLOW: u32 = 0x7fff_0000;
+
+/// A single classic-BPF instruction (`struct sock_filter`).
+#[derive(Debug, Copy, Clone)]
+pub struct SockFilter {
+    pub code: u16,
+    pub jt: u8,
+    pub jf: u8,
+    pub k: u32,
+}
+
+/// The synthesized `struct seccomp_data` a filter program runs against,
+/// assembled from the dispatched syscall number and X0-X5.
+struct SeccompData {
+    nr: u32,
+    arch: u32,
+    instruction_pointer: u64,
+    args: [u64; 6],
+}
+
+impl SeccompData {
+    fn to_bytes(&self) -> [u8; 64] {
+        let mut buf = [0u8; 64];
+        buf[0..4].copy_from_slice(&self.nr.to_le_bytes());
+        buf[4..8].copy_from_slice(&self.arch.to_le_bytes());
+        buf[8..16].copy_from_slice(&self.instruction_pointer.to_le_bytes());
+        for (i, arg) in self.args.iter().enumerate() {
+            buf[16 + i * 8..24 + i * 8].copy_from_slice(&arg.to_le_bytes());
+        }
+        buf
+    }
+}
+
+/// The effect a seccomp filter chain has on the syscall about to be dispatched.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum SeccompAction {
+    Allow,
+    Errno(u16),
+    Trap,
+    Kill,
+}
+
+impl SeccompAction {
+    /// Priority used when merging the verdicts of a filter chain: kill beats
+    /// trap beats errno beats allow, matching the kernel's own precedence.
+    fn rank(&self) -> u8 {
+        match self {
+            SeccompAction::Kill => 3,
+            SeccompAction::Trap => 2,
+            SeccompAction::Errno(_) => 1,
+            SeccompAction::Allow => 0,
+        }
+    }
+
+    fn from_return_value(ret: u32) -> SeccompAction {
+        let data = (ret & SECCOMP_RET_DATA) as u16;
+        match ret & SECCOMP_RET_ACTION_FULL {
+            SECCOMP_RET_ALLOW => SeccompAction::Allow,
+            SECCOMP_RET_ERRNO => SeccompAction::Errno(data),
+            SECCOMP_RET_TRAP => SeccompAction::Trap,
+            SECCOMP_RET_KILL_THREAD => SeccompAction::Kill,
+            _ => SeccompAction::Allow,
+        }
+    }
+}
+
+/// Execute a classic-BPF program against a synthesized `seccomp_data` record:
+/// a 32-bit accumulator, an index register, and 16 words of scratch memory.
+fn run_bpf_filter(filter: &[SockFilter], data: &SeccompData) -> u32 {
+    let bytes = data.to_bytes();
+    let mut a: u32 = 0;
+    let mut x: u32 = 0;
+    let mut mem = [0u32; 16];
+    let mut pc: usize = 0;
+
+    loop {
+        let insn = match filter.get(pc) {
+            Some(insn) => *insn,
+            None => return SECCOMP_RET_KILL_THREAD,
+        };
+        match insn.code & BPF_CLASS_MASK {
+            BPF_LD => {
+                a = match insn.code & BPF_MODE_MASK {
+                    BPF_IMM => insn.k,
+                    BPF_ABS => {
+                        let off = insn.k as usize;
+                        bytes.get(off..off + 4)
+                            .map(|w| u32::from_le_bytes(w.try_into().unwrap()))
+                            .unwrap_or(0)
+                    }
+                    BPF_MEM => mem[insn.k as usize & 0xf],
+                    _ => 0,
+                };
+                pc += 1;
+            }
+            BPF_LDX => {
+                x = match insn.code & BPF_MODE_MASK {
+                    BPF_IMM => insn.k,
+                    BPF_MEM => mem[insn.k as usize & 0xf],
+                    _ => 0,
+                };
+                pc += 1;
+            }
+            BPF_ST => {
+                mem[insn.k as usize & 0xf] = a;
+                pc += 1;
+            }
+            BPF_STX => {
+                mem[insn.k as usize & 0xf] = x;
+                pc += 1;
+            }
+            BPF_ALU => {
+                let operand = if insn.code & BPF_SRC_MASK == BPF_X { x } else { insn.k };
+                a = match insn.code & BPF_OP_MASK {
+                    BPF_ADD => a.wrapping_add(operand),
+                    BPF_SUB => a.wrapping_sub(operand),
+                    BPF_MUL => a.wrapping_mul(operand),
+                    BPF_DIV => if operand == 0 { 0 } else { a / operand },
+                    BPF_MOD => if operand == 0 { 0 } else { a % operand },
+                    BPF_OR => a | operand,
+                    BPF_AND => a & operand,
+                    BPF_XOR => a ^ operand,
+                    BPF_LSH => a.wrapping_shl(operand),
+                    BPF_RSH => a.wrapping_shr(operand),
+                    BPF_NEG => (a as i32).wrapping_neg() as u32,
+                    _ => a,
+                };
+                pc += 1;
+            }
+            BPF_JMP => {
+                let op = insn.code & BPF_OP_MASK;
+                if op == BPF_JA {
+                    pc += 1 + insn.k as usize;
+                    continue;
+                }
+                let operand = if insn.code & BPF_SRC_MASK == BPF_X { x } else { insn.k };
+                let taken = match op {
+                    BPF_JEQ => a == operand,
+                    BPF_JGT => a > operand,
+                    BPF_JGE => a >= operand,
+                    BPF_JSET => a & operand != 0,
+                    _ => false,
+                };
+                pc += 1 + if taken { insn.jt as usize } else { insn.jf as usize };
+            }
+            BPF_RET => {
+                return if insn.code & BPF_SRC_MASK == BPF_X { x } else { insn.k };
+            }
+            _ => return SECCOMP_RET_KILL_THREAD,
+        }
+    }
+}
+
+/// Evaluate every installed filter against the syscall about to be
+/// dispatched and return the most restrictive verdict, as the kernel does
+/// when multiple filters are layered onto one thread.
+fn evaluate_seccomp<T: Clone>(nr: Syscalls, backend: &Backend<T>, emulator: &AndroidEmulator<T>) -> SeccompAction {
+    let filters = emulator.inner_mut().seccomp_filters.clone();
+    if filters.is_empty() {
+        return SeccompAction::Allow;
+    }
+
+    let data = SeccompData {
+        nr: nr as u32,
+        arch: AUDIT_ARCH_AARCH64,
+        instruction_pointer: backend.reg_read(RegisterARM64::PC).unwrap_or(0),
+        args: [
+            backend.reg_read(RegisterARM64::X0).unwrap_or(0),
+            backend.reg_read(RegisterARM64::X1).unwrap_or(0),
+            backend.reg_read(RegisterARM64::X2).unwrap_or(0),
+            backend.reg_read(RegisterARM64::X3).unwrap_or(0),
+            backend.reg_read(RegisterARM64::X4).unwrap_or(0),
+            backend.reg_read(RegisterARM64::X5).unwrap_or(0),
+        ],
+    };
+
+    filters.iter()
+        .map(|filter| SeccompAction::from_return_value(run_bpf_filter(filter, &data)))
+        .max_by_key(SeccompAction::rank)
+        .unwrap_or(SeccompAction::Allow)
+}
+
+/// Parse a guest `struct sock_fprog { u16 len; struct sock_filter *filter; }`
+/// at `fprog_addr` and prepend the resulting program to the filter chain, so
+/// it is evaluated before any filter installed earlier (the kernel runs the
+/// most recently attached filter's decision through all older ones too, but
+/// attaching can only add restrictions, so evaluating newest-first and
+/// taking the most restrictive verdict across the chain is equivalent).
+fn install_seccomp_program<T: Clone>(backend: &Backend<T>, emulator: &AndroidEmulator<T>, fprog_addr: u64) {
+    let mut len_buf = [0u8; 2];
+    backend.mem_read(fprog_addr, &mut len_buf).unwrap();
+    let len = u16::from_le_bytes(len_buf) as usize;
+
+    let mut ptr_buf = [0u8; 8];
+    backend.mem_read(fprog_addr + 8, &mut ptr_buf).unwrap();
+    let filter_addr = u64::from_le_bytes(ptr_buf);
+
+    let mut program = Vec::with_capacity(len);
+    for i in 0..len {
+        let mut insn_buf = [0u8; 8];
+        backend.mem_read(filter_addr + (i as u64) * 8, &mut insn_buf).unwrap();
+        program.push(SockFilter {
+            code: u16::from_le_bytes([insn_buf[0], insn_buf[1]]),
+            jt: insn_buf[2],
+            jf: insn_buf[3],
+            k: u32::from_le_bytes([insn_buf[4], insn_buf[5], insn_buf[6], insn_buf[7]]),
+        });
+    }
+
+    emulator.inner_mut().seccomp_filters.insert(0, Rc::new(program));
+}
+
+/// One decoded syscall dispatch, handed to a sink installed via
+/// [`AndroidEmulator::set_syscall_trace_sink`].
+#[derive(Debug, Clone)]
+pub struct SyscallTraceEvent {
+    pub nr: Syscalls,
+    pub args: String,
+    pub ret: i64,
+    pub duration: std::time::Duration,
+}
+
+pub type SyscallTraceSink<T> = Rc<dyn Fn(&SyscallTraceEvent, &AndroidEmulator<T>)>;
+
+/// Tracing configuration: an optional sink plus an optional allow-list of
+/// syscall names (`format!("{:?}", nr)`, e.g. `"__NR_openat"`) restricting
+/// which dispatches get decoded and reported. Lives as `Inner::syscall_tracer`
+/// and is reached through `emulator.inner_mut()`.
+#[derive(Default)]
+pub(crate) struct SyscallTracer<T> {
+    sink: Option<SyscallTraceSink<T>>,
+    filter: Option<std::collections::HashSet<String>>,
+}
+
+impl<T> SyscallTracer<T> {
+    fn wants(&self, nr: Syscalls) -> bool {
+        self.sink.is_some() && self.filter.as_ref().map_or(true, |names| names.contains(&format!("{:?}", nr)))
+    }
+}
+
+/// Format an X0 return value the way strace does: the raw value, or
+/// `-1 <errno>` when it falls in the kernel's reserved errno range.
+fn format_syscall_ret(ret: i64) -> String {
+    if (-4095..0).contains(&ret) {
+        format!("-1 (errno {})", -ret)
+    } else {
+        ret.to_string()
+    }
+}
+
+/// Read a NUL-terminated guest C string, capped well above any real path or
+/// name so a bad pointer can't turn tracing into an unbounded read.
+fn read_c_string<T: Clone>(backend: &Backend<T>, addr: u64) -> String {
+    if addr == 0 {
+        return "NULL".to_string();
+    }
+    let mut bytes = Vec::new();
+    for i in 0..4096u64 {
+        let mut byte = [0u8; 1];
+        if backend.mem_read(addr + i, &mut byte).is_err() || byte[0] == 0 {
+            break;
+        }
+        bytes.push(byte[0]);
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// Decode a dispatched syscall's arguments per a small per-syscall table,
+/// falling back to raw hex args for anything not worth a bespoke format.
+fn decode_syscall_args<T: Clone>(nr: Syscalls, backend: &Backend<T>) -> String {
+    let arg = |r| backend.reg_read(r).unwrap_or(0);
+    match nr {
+        Syscalls::__NR_openat => format!(
+            "dirfd={}, path={:?}, flags=0x{:x}, mode=0{:o}",
+            arg(RegisterARM64::X0) as i64,
+            read_c_string(backend, arg(RegisterARM64::X1)),
+            arg(RegisterARM64::X2),
+            arg(RegisterARM64::X3),
+        ),
+        Syscalls::__NR3264_mmap => format!(
+            "addr=0x{:x}, len={}, prot=0x{:x}, flags=0x{:x}, fd={}, offset=0x{:x}",
+            arg(RegisterARM64::X0), arg(RegisterARM64::X1), arg(RegisterARM64::X2),
+            arg(RegisterARM64::X3), arg(RegisterARM64::X4) as i64, arg(RegisterARM64::X5),
+        ),
+        Syscalls::__NR_munmap => format!(
+            "addr=0x{:x}, len={}",
+            arg(RegisterARM64::X0), arg(RegisterARM64::X1),
+        ),
+        Syscalls::__NR_socket => format!(
+            "domain={}, type={}, protocol={}",
+            arg(RegisterARM64::X0), arg(RegisterARM64::X1), arg(RegisterARM64::X2),
+        ),
+        Syscalls::__NR_close => format!("fd={}", arg(RegisterARM64::X0) as i64),
+        Syscalls::__NR_read | Syscalls::__NR_write => format!(
+            "fd={}, buf=0x{:x}, count={}",
+            arg(RegisterARM64::X0) as i64, arg(RegisterARM64::X1), arg(RegisterARM64::X2),
+        ),
+        _ => format!(
+            "a0=0x{:x}, a1=0x{:x}, a2=0x{:x}, a3=0x{:x}",
+            arg(RegisterARM64::X0), arg(RegisterARM64::X1), arg(RegisterARM64::X2), arg(RegisterARM64::X3),
+        ),
+    }
+}
+
+const SOL_SOCKET: u32 = 1;
+const SCM_RIGHTS: u32 = 1;
+const MSG_CTRUNC: u32 = 0x08;
+const MSG_TRUNC: u32 = 0x20;
+const ENOTCONN: i64 = 107;
+
+/// One `sendmsg`'d payload sitting in a peer's mailbox: the bytes from the
+/// sender's iovecs plus any `SCM_RIGHTS` fds carried alongside them.
+struct PendingMessage {
+    data: Vec<u8>,
+    fds: Vec<u64>,
+}
+
+/// Per-unix-socket-fd ancillary-data state: which fd is whose peer, the
+/// per-receiver mailbox of pending messages, and the guest fds this
+/// subsystem has minted to alias a transferred descriptor.
+#[derive(Default)]
+pub(crate) struct ScmRightsState {
+    peers: HashMap<u64, u64>,
+    pending: HashMap<u64, std::collections::VecDeque<PendingMessage>>,
+    aliases: HashMap<u64, u64>,
+    next_alias: u64,
+}
+
+impl ScmRightsState {
+    /// Mint a new guest fd that aliases `original`, the way `dup` would.
+    /// Picked from a range well above normal small-integer fds to avoid
+    /// colliding with the real fd table.
+    fn mint_fd(&mut self, original: u64) -> u64 {
+        self.next_alias += 1;
+        let fd = 0x4000_0000 + self.next_alias;
+        self.aliases.insert(fd, original);
+        fd
+    }
+
+    /// Resolve a fd minted by [`ScmRightsState::mint_fd`] back to the real fd
+    /// it aliases, so a later syscall issued against the alias reaches the
+    /// object it actually names instead of a number nothing else recognizes.
+    fn resolve(&self, fd: u64) -> Option<u64> {
+        self.aliases.get(&fd).copied()
+    }
+}
+
+fn read_u64<T: Clone>(backend: &Backend<T>, addr: u64) -> u64 {
+    let mut buf = [0u8; 8];
+    backend.mem_read(addr, &mut buf).unwrap();
+    u64::from_le_bytes(buf)
+}
+
+fn read_u32<T: Clone>(backend: &Backend<T>, addr: u64) -> u32 {
+    let mut buf = [0u8; 4];
+    backend.mem_read(addr, &mut buf).unwrap();
+    u32::from_le_bytes(buf)
+}
+
+fn write_u64<T: Clone>(backend: &Backend<T>, addr: u64, value: u64) {
+    backend.mem_write(addr, &value.to_le_bytes()).unwrap();
+}
+
+fn write_u32<T: Clone>(backend: &Backend<T>, addr: u64, value: u32) {
+    backend.mem_write(addr, &value.to_le_bytes()).unwrap();
+}
+
+fn cmsg_align(len: u64) -> u64 {
+    (len + 7) & !7
+}
+
+// Guest `struct msghdr` field offsets (arm64 LP64 layout).
+const MSGHDR_IOV: u64 = 16;
+const MSGHDR_IOVLEN: u64 = 24;
+const MSGHDR_CONTROL: u64 = 32;
+const MSGHDR_CONTROLLEN: u64 = 40;
+const MSGHDR_FLAGS: u64 = 48;
+
+/// Walk a raw `msg_control` buffer's `cmsghdr` chain and pull out the fds of
+/// every `SOL_SOCKET`/`SCM_RIGHTS` entry. Pure byte-level logic so it can be
+/// tested without a `Backend`; [`parse_scm_rights`] just supplies the bytes.
+fn parse_scm_rights_bytes(buf: &[u8]) -> Vec<u64> {
+    let mut fds = Vec::new();
+    let mut offset = 0usize;
+    while offset + 16 <= buf.len() {
+        let cmsg_len = u64::from_le_bytes(buf[offset..offset + 8].try_into().unwrap());
+        let cmsg_level = u32::from_le_bytes(buf[offset + 8..offset + 12].try_into().unwrap());
+        let cmsg_type = u32::from_le_bytes(buf[offset + 12..offset + 16].try_into().unwrap());
+        if cmsg_len < 16 || offset + cmsg_len as usize > buf.len() {
+            break;
+        }
+        if cmsg_level == SOL_SOCKET && cmsg_type == SCM_RIGHTS {
+            let count = (cmsg_len - 16) / 4;
+            for i in 0..count {
+                let p = offset + 16 + (i * 4) as usize;
+                fds.push(u32::from_le_bytes(buf[p..p + 4].try_into().unwrap()) as u64);
+            }
+        }
+        offset += cmsg_align(cmsg_len) as usize;
+    }
+    fds
+}
+
+/// Walk the `msg_control` buffer's `cmsghdr` chain and pull out the fds of
+/// every `SOL_SOCKET`/`SCM_RIGHTS` entry.
+fn parse_scm_rights<T: Clone>(backend: &Backend<T>, control_addr: u64, control_len: u64) -> Vec<u64> {
+    let mut buf = vec![0u8; control_len as usize];
+    backend.mem_read(control_addr, &mut buf).unwrap();
+    parse_scm_rights_bytes(&buf)
+}
+
+/// Build a single `SOL_SOCKET`/`SCM_RIGHTS` cmsghdr carrying `fds`, already
+/// padded to `cmsg_align`. Pure byte-level logic so it can be tested without
+/// a `Backend`; [`write_scm_rights`] just writes the bytes out.
+fn write_scm_rights_bytes(fds: &[u64]) -> Vec<u8> {
+    let cmsg_len = 16 + fds.len() as u64 * 4;
+    let mut buf = vec![0u8; cmsg_align(cmsg_len) as usize];
+    buf[0..8].copy_from_slice(&cmsg_len.to_le_bytes());
+    buf[8..12].copy_from_slice(&SOL_SOCKET.to_le_bytes());
+    buf[12..16].copy_from_slice(&SCM_RIGHTS.to_le_bytes());
+    for (i, &fd) in fds.iter().enumerate() {
+        let p = 16 + i * 4;
+        buf[p..p + 4].copy_from_slice(&(fd as u32).to_le_bytes());
+    }
+    buf
+}
+
+/// Write a single `SOL_SOCKET`/`SCM_RIGHTS` cmsghdr carrying `fds`, returning
+/// the number of bytes written: the cmsghdr rounded up to `cmsg_align`, cut
+/// short at `control_len` so nothing lands past the guest's control buffer.
+fn write_scm_rights<T: Clone>(backend: &Backend<T>, control_addr: u64, control_len: u64, fds: &[u64]) -> u64 {
+    let buf = write_scm_rights_bytes(fds);
+    let len = buf.len().min(control_len as usize);
+    backend.mem_write(control_addr, &buf[..len]).unwrap();
+    len as u64
+}
+
+/// Read the concatenated bytes of every iovec in `[iov_addr, iov_addr + iov_len)`.
+fn read_iovec_bytes<T: Clone>(backend: &Backend<T>, iov_addr: u64, iov_len: u64) -> Vec<u8> {
+    let mut data = Vec::new();
+    for i in 0..iov_len {
+        let base = read_u64(backend, iov_addr + i * 16);
+        let len = read_u64(backend, iov_addr + i * 16 + 8);
+        let mut chunk = vec![0u8; len as usize];
+        backend.mem_read(base, &mut chunk).unwrap();
+        data.extend_from_slice(&chunk);
+    }
+    data
+}
+
+/// For each iovec capacity in order, how many bytes of a `data_len`-byte
+/// message land in it. This is the copy-length math `recvmsg` depends on —
+/// an earlier version of this code summed iovec *capacity* instead of
+/// tracking actual bytes transferred here, which over-reported the copy
+/// size whenever a message didn't fill every iovec. Pure so it's testable
+/// without a `Backend`; [`write_iovec_bytes`] does the actual memory writes.
+fn plan_iovec_copy(capacities: &[u64], data_len: usize) -> (Vec<usize>, bool) {
+    let mut remaining = data_len;
+    let mut takes = Vec::with_capacity(capacities.len());
+    for &cap in capacities {
+        let take = if remaining == 0 { 0 } else { (cap as usize).min(remaining) };
+        takes.push(take);
+        remaining -= take;
+    }
+    (takes, remaining > 0)
+}
+
+/// Copy `data` into the receiver's iovecs in order, stopping once `data` is
+/// exhausted or the iovecs run out of room. Returns the number of bytes
+/// actually copied and whether `data` had bytes left over (`MSG_TRUNC`).
+fn write_iovec_bytes<T: Clone>(backend: &Backend<T>, iov_addr: u64, iov_len: u64, data: &[u8]) -> (usize, bool) {
+    let capacities: Vec<u64> = (0..iov_len).map(|i| read_u64(backend, iov_addr + i * 16 + 8)).collect();
+    let (takes, truncated) = plan_iovec_copy(&capacities, data.len());
+    let mut written = 0usize;
+    for (i, &take) in takes.iter().enumerate() {
+        if take == 0 {
+            continue;
+        }
+        let base = read_u64(backend, iov_addr + i as u64 * 16);
+        backend.mem_write(base, &data[written..written + take]).unwrap();
+        written += take;
+    }
+    (written, truncated)
+}
+
+/// `sendmsg`: copies the sender's iovec bytes and any `SCM_RIGHTS` fds into
+/// the connected peer's mailbox (see [`AndroidEmulator::register_unix_socket_peers`]).
+/// Fails with `ENOTCONN` if `fd` has no registered peer, since there is
+/// nowhere for the message to go.
+fn syscall_sendmsg<T: Clone>(backend: &Backend<T>, emulator: &AndroidEmulator<T>) {
+    let fd = backend.reg_read(RegisterARM64::X0).unwrap();
+    let msg_addr = backend.reg_read(RegisterARM64::X1).unwrap();
+
+    let iov_addr = read_u64(backend, msg_addr + MSGHDR_IOV);
+    let iov_len = read_u64(backend, msg_addr + MSGHDR_IOVLEN);
+    let control_addr = read_u64(backend, msg_addr + MSGHDR_CONTROL);
+    let control_len = read_u64(backend, msg_addr + MSGHDR_CONTROLLEN);
+
+    let data = read_iovec_bytes(backend, iov_addr, iov_len);
+    let fds = if control_len > 0 {
+        parse_scm_rights(backend, control_addr, control_len)
+    } else {
+        Vec::new()
+    };
+
+    let state = &mut emulator.inner_mut().scm_rights;
+    match state.peers.get(&fd).copied() {
+        Some(peer) => {
+            let sent = data.len() as i64;
+            state.pending.entry(peer).or_default().push_back(PendingMessage { data, fds });
+            backend.reg_write_i64(RegisterARM64::X0, sent).unwrap();
+        }
+        None => {
+            warn!("sendmsg: fd {} has no registered unix peer, dropping", fd);
+            backend.reg_write_i64(RegisterARM64::X0, -ENOTCONN).unwrap();
+        }
+    }
+}
+
+/// `recvmsg`: pops the next pending message for `fd`, copying its payload
+/// into the receiver's iovecs and, if it carried `SCM_RIGHTS` fds, minting
+/// aliasing guest fds and writing them back as a cmsghdr (truncating, and
+/// setting `MSG_CTRUNC`, if the guest's control buffer is too small). When
+/// there is no pending message this returns `0` rather than reporting a
+/// successful read of whatever was already sitting in the guest buffers.
+fn syscall_recvmsg<T: Clone>(backend: &Backend<T>, emulator: &AndroidEmulator<T>) {
+    let fd = backend.reg_read(RegisterARM64::X0).unwrap();
+    let msg_addr = backend.reg_read(RegisterARM64::X1).unwrap();
+
+    let iov_addr = read_u64(backend, msg_addr + MSGHDR_IOV);
+    let iov_len = read_u64(backend, msg_addr + MSGHDR_IOVLEN);
+    let control_addr = read_u64(backend, msg_addr + MSGHDR_CONTROL);
+    let control_len = read_u64(backend, msg_addr + MSGHDR_CONTROLLEN);
+
+    let message = match emulator.inner_mut().scm_rights.pending.get_mut(&fd).and_then(|q| q.pop_front()) {
+        Some(message) => message,
+        None => {
+            write_u64(backend, msg_addr + MSGHDR_CONTROLLEN, 0);
+            write_u32(backend, msg_addr + MSGHDR_FLAGS, 0);
+            backend.reg_write_i64(RegisterARM64::X0, 0).unwrap();
+            return;
+        }
+    };
+
+    let (copied, data_truncated) = write_iovec_bytes(backend, iov_addr, iov_len, &message.data);
+    let mut msg_flags = if data_truncated { MSG_TRUNC } else { 0 };
+    let mut controllen_out = 0u64;
+
+    if !message.fds.is_empty() {
+        let aliased: Vec<u64> = {
+            let state = &mut emulator.inner_mut().scm_rights;
+            message.fds.iter().map(|&orig| state.mint_fd(orig)).collect()
+        };
+        let fit = ((control_len.saturating_sub(16)) / 4) as usize;
+        if fit < aliased.len() {
+            msg_flags |= MSG_CTRUNC;
+        }
+        let truncated = &aliased[..fit.min(aliased.len())];
+        if !truncated.is_empty() {
+            controllen_out = write_scm_rights(backend, control_addr, control_len, truncated);
+        }
+    }
+
+    write_u64(backend, msg_addr + MSGHDR_CONTROLLEN, controllen_out);
+    write_u32(backend, msg_addr + MSGHDR_FLAGS, msg_flags);
+    backend.reg_write_i64(RegisterARM64::X0, copied as i64).unwrap();
+}
+
 #[inline]
 fn syscall<'a, T: Clone>(nr: Syscalls, backend: &Backend<'a, T>, emulator: &AndroidEmulator<'a, T>)  {
+    if !emulator.inner_mut().syscall_tracer.wants(nr) {
+        dispatch_syscall(nr, backend, emulator);
+        return;
+    }
+
+    let args = decode_syscall_args(nr, backend);
+    let start = std::time::Instant::now();
+    dispatch_syscall(nr, backend, emulator);
+    let event = SyscallTraceEvent {
+        nr,
+        args,
+        ret: backend.reg_read(RegisterARM64::X0).unwrap_or(0) as i64,
+        duration: start.elapsed(),
+    };
+    if let Some(sink) = emulator.inner_mut().syscall_tracer.sink.clone() {
+        sink(&event, emulator);
+    }
+}
+
+#[inline]
+fn dispatch_syscall<'a, T: Clone>(nr: Syscalls, backend: &Backend<'a, T>, emulator: &AndroidEmulator<'a, T>)  {
     if option_env!("EMU_LOG") == Some("1") {
         info!("syscall: {:?}", nr);
     }
+
+    match evaluate_seccomp(nr, backend, emulator) {
+        SeccompAction::Allow => {}
+        SeccompAction::Errno(errno) => {
+            backend.reg_write_i64(RegisterARM64::X0, -(errno as i64)).unwrap();
+            return;
+        }
+        SeccompAction::Trap => {
+            // Real SECCOMP_RET_TRAP delivers a catchable SIGSYS instead of
+            // unconditionally killing the thread, but this crate has no
+            // signal-delivery path to deliver it through, so it currently
+            // collapses to the same halt as SeccompAction::Kill below.
+            warn!("seccomp: SECCOMP_RET_TRAP for syscall {:?} (no SIGSYS delivery path, halting)", nr);
+            backend.emu_stop(TaskStatus::X, emulator)
+                .expect("failed to stop emulator");
+            return;
+        }
+        SeccompAction::Kill => {
+            warn!("seccomp: SECCOMP_RET_KILL for syscall {:?}", nr);
+            backend.emu_stop(TaskStatus::X, emulator)
+                .expect("failed to stop emulator");
+            return;
+        }
+    }
+
+    // A transferred SCM_RIGHTS fd is only ever a guest-visible alias (see
+    // `ScmRightsState::mint_fd`) with no entry in the real fd table, so
+    // translate it back to the fd it actually names before dispatch reaches
+    // a handler that operates on that table. read/write/close/etc. take their
+    // fd in X0, so this one rewrite covers them without teaching each handler
+    // about the mailbox; an fd passed in another register (mmap's, in X4) is
+    // not translated. Placed after the
+    // seccomp check above so filters still see the fd value the guest
+    // itself used.
+    let x0 = backend.reg_read(RegisterARM64::X0).unwrap_or(0);
+    if let Some(real_fd) = emulator.inner_mut().scm_rights.resolve(x0) {
+        backend.reg_write_i64(RegisterARM64::X0, real_fd as i64).unwrap();
+    }
+
+    // Checked ahead of the seccomp-install special cases below so a handler
+    // registered for __NR_seccomp/__NR_prctl via register_syscall_override
+    // actually takes priority over them, as its doc comment promises —
+    // intercepting those two is exactly how a caller would implement an
+    // anti-debug bypass that defeats a target installing its own filter.
+    if let Some(handler) = emulator.inner_mut().syscall_registry.get(nr).cloned() {
+        handler(backend, emulator);
+        return;
+    }
+
+    if nr == Syscalls::__NR_seccomp {
+        // seccomp(unsigned int operation, unsigned int flags, void *args)
+        let fprog_addr = backend.reg_read(RegisterARM64::X2).unwrap();
+        install_seccomp_program(backend, emulator, fprog_addr);
+        backend.reg_write_i64(RegisterARM64::X0, 0).unwrap();
+        return;
+    }
+    if nr == Syscalls::__NR_prctl
+        && backend.reg_read(RegisterARM64::X0).unwrap() == PR_SET_SECCOMP
+        && backend.reg_read(RegisterARM64::X1).unwrap() == SECCOMP_MODE_FILTER {
+        let fprog_addr = backend.reg_read(RegisterARM64::X2).unwrap();
+        install_seccomp_program(backend, emulator, fprog_addr);
+        backend.reg_write_i64(RegisterARM64::X0, 0).unwrap();
+        return;
+    }
+
     let _ = match nr {
         Syscalls::__NR_openat => {
             syscalls::syscall_openat(backend, emulator);
@@ -199,10 +945,10 @@ fn syscall<'a, T: Clone>(nr: Syscalls, backend: &Backend<'a, T>, emulator: &Andr
             syscalls::syscall_brk(backend, emulator);
         }
         Syscalls::__NR_munmap => {
-            syscalls::syscall_munmap(backend, emulator);
+            syscall_munmap(backend, emulator);
         }
         Syscalls::__NR3264_mmap => {
-            syscalls::syscall_mmap(backend, emulator);
+            syscall_mmap(backend, emulator);
         }
         Syscalls::__NR_mprotect => {
             syscalls::syscall_mprotect(backend, emulator);
@@ -267,13 +1013,336 @@ fn syscall<'a, T: Clone>(nr: Syscalls, backend: &Backend<'a, T>, emulator: &Andr
         Syscalls::__NR_pipe2 => {
             syscalls::syscall_pipe2(backend, emulator);
         }
+        Syscalls::__NR_sendmsg => {
+            syscall_sendmsg(backend, emulator);
+        }
+        Syscalls::__NR_recvmsg => {
+            syscall_recvmsg(backend, emulator);
+        }
         _ => {
-            info!("Unsupported syscall: {:?}", nr);
-            backend.emu_stop(TaskStatus::X, emulator)
-                .expect("failed to stop emulator");
-            panic!("Unsupported syscall: {:?}", nr);
+            match emulator.inner_mut().syscall_registry.unsupported_policy {
+                UnsupportedSyscallPolicy::Enosys => {
+                    warn!("Unsupported syscall: {:?}, returning -ENOSYS", nr);
+                    backend.reg_write_i64(RegisterARM64::X0, -ENOSYS).unwrap();
+                }
+                UnsupportedSyscallPolicy::Panic => {
+                    info!("Unsupported syscall: {:?}", nr);
+                    backend.emu_stop(TaskStatus::X, emulator)
+                        .expect("failed to stop emulator");
+                    panic!("Unsupported syscall: {:?}", nr);
+                }
+            }
+        }
+    };
+}
+
+impl<T: Clone> AndroidEmulator<'_, T> {
+    /// Install `handler` as a stand-in for syscall `nr`, taking priority over
+    /// the crate's built-in dispatch in `syscall()`. Lets callers stub out or
+    /// intercept syscalls (anti-debug bypasses, custom behavior for a target)
+    /// without patching this crate.
+    pub fn register_syscall_override<F>(&self, nr: Syscalls, handler: F)
+        where F: Fn(&Backend<T>, &AndroidEmulator<T>) + 'static {
+        self.inner_mut().syscall_registry.overrides.insert(nr as u64, Rc::new(handler));
+    }
+
+    /// Set what happens when a dispatched syscall has no override and no
+    /// built-in handler. Defaults to [`UnsupportedSyscallPolicy::Panic`].
+    pub fn set_unsupported_syscall_policy(&self, policy: UnsupportedSyscallPolicy) {
+        self.inner_mut().syscall_registry.unsupported_policy = policy;
+    }
+
+    /// Toggle randomized placement for `mmap` calls that don't specify
+    /// `MAP_FIXED`. An address hint without `MAP_FIXED` is not consulted:
+    /// such calls are placed like unhinted ones. Off by default so runs stay
+    /// reproducible; `syscall_mmap`/`syscall_munmap` consult
+    /// [`RangeAllocator`] through `emulator.inner_mut().range_allocator`.
+    pub fn set_mmap_randomization(&self, randomize: bool) {
+        self.inner_mut().range_allocator.randomize = randomize;
+    }
+
+    /// Install a sink that receives every traced syscall's decoded
+    /// arguments, return value, and elapsed time. Combine with
+    /// [`AndroidEmulator::set_syscall_trace_filter`] to watch only the
+    /// syscalls you care about; this turns the emulator into a strace-like
+    /// tool for reverse engineering a target.
+    pub fn set_syscall_trace_sink<F>(&self, sink: F)
+        where F: Fn(&SyscallTraceEvent, &AndroidEmulator<T>) + 'static {
+        self.inner_mut().syscall_tracer.sink = Some(Rc::new(sink));
+    }
+
+    /// Restrict tracing to the given syscall names (`format!("{:?}", nr)`,
+    /// e.g. `"__NR_openat"`). Pass `None` to trace every syscall again.
+    pub fn set_syscall_trace_filter(&self, names: Option<std::collections::HashSet<String>>) {
+        self.inner_mut().syscall_tracer.filter = names;
+    }
+
+    /// Convenience sink that logs each trace event through `log::info!`,
+    /// formatted like strace: `__NR_openat(dirfd=-100, path="...") = 3 <12.3µs>`.
+    pub fn enable_default_syscall_trace(&self) {
+        self.set_syscall_trace_sink(|event, _| {
+            info!("{:?}({}) = {} <{:?}>", event.nr, event.args, format_syscall_ret(event.ret), event.duration);
+        });
+    }
+
+    /// Record that `fd_a` and `fd_b` are the two connected ends of a unix
+    /// socket (e.g. a `socketpair`), so a `sendmsg` carrying SCM_RIGHTS on
+    /// one end lands in the other end's `recvmsg` mailbox. No syscall handler
+    /// calls this: `socketpair` is not dispatched and `connect` does not
+    /// register peers, so two fds are paired only once this is called.
+    pub fn register_unix_socket_peers(&self, fd_a: u64, fd_b: u64) {
+        let inner = self.inner_mut();
+        inner.scm_rights.peers.insert(fd_a, fd_b);
+        inner.scm_rights.peers.insert(fd_b, fd_a);
+    }
+}
+
+const PAGE_SIZE: u64 = 0x1000;
+
+/// Address-space allocator over the managed mmap VA window. Keeps a sorted
+/// free-list of `[base, size)` ranges so `munmap`'d space is reused, honors
+/// `MAP_FIXED` by carving the exact requested range, and can place anonymous
+/// mappings at a randomized base for ASLR-sensitive targets.
+pub(crate) struct RangeAllocator {
+    window_base: u64,
+    window_end: u64,
+    /// Sorted, non-overlapping `(base, size)` free ranges.
+    free: Vec<(u64, u64)>,
+    /// Sorted, non-overlapping `(base, size)` live allocations.
+    allocated: Vec<(u64, u64)>,
+    /// When set, [`RangeAllocator::allocate`] picks a random base instead of
+    /// the lowest best fit.
+    randomize: bool,
+    rng_state: u64,
+}
+
+impl RangeAllocator {
+    /// Entropy for `rng_state` comes from wall-clock time and pid rather
+    /// than the window bounds alone, so randomized placement (see
+    /// [`AndroidEmulator::set_mmap_randomization`]) actually varies from run
+    /// to run instead of replaying the same "random" sequence every time
+    /// against the same VA window. Use [`RangeAllocator::new_seeded`] when a
+    /// reproducible sequence is wanted, e.g. in tests.
+    pub fn new(window_base: u64, window_end: u64) -> Self {
+        let entropy = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0)
+            ^ (std::process::id() as u64).wrapping_mul(0x9E3779B97F4A7C15);
+        Self::new_seeded(window_base, window_end, entropy)
+    }
+
+    /// Like [`RangeAllocator::new`] but with an explicit RNG seed, so
+    /// randomized placement can be replayed deterministically in tests.
+    pub fn new_seeded(window_base: u64, window_end: u64, seed: u64) -> Self {
+        RangeAllocator {
+            window_base,
+            window_end,
+            free: vec![(window_base, window_end - window_base)],
+            allocated: Vec::new(),
+            randomize: false,
+            rng_state: seed ^ window_base ^ window_end,
+        }
+    }
+
+    fn round_up_to_page(size: u64) -> u64 {
+        (size + PAGE_SIZE - 1) & !(PAGE_SIZE - 1)
+    }
+
+    /// Allocate `size` bytes without a fixed address: randomized placement
+    /// when enabled, otherwise the smallest free range that fits (the
+    /// lowest-addressed one among equally sized candidates).
+    pub fn allocate(&mut self, size: u64) -> Option<u64> {
+        let size = Self::round_up_to_page(size);
+        if size == 0 || size > self.window_end - self.window_base {
+            return None;
+        }
+        if self.randomize {
+            self.allocate_randomized(size)
+        } else {
+            self.allocate_best_fit(size)
+        }
+    }
+
+    /// Carve out the exact `[base, base+size)` range, unmapping any existing
+    /// allocation that overlaps it first. Used for `MAP_FIXED`.
+    pub fn allocate_specific(&mut self, base: u64, size: u64) -> Option<u64> {
+        let size = Self::round_up_to_page(size);
+        let base = base & !(PAGE_SIZE - 1);
+        if size == 0 || base < self.window_base || base + size > self.window_end {
+            return None;
+        }
+        self.free(base, size);
+        self.carve_free(base, size);
+        self.mark_allocated(base, size);
+        Some(base)
+    }
+
+    /// Return `[base, base+size)` to the free list, splitting any
+    /// allocation it only partially overlaps and coalescing adjacent free
+    /// ranges. Partial unmaps split the remaining allocation in two.
+    pub fn free(&mut self, base: u64, size: u64) {
+        let size = Self::round_up_to_page(size);
+        let end = base + size;
+        let mut remaining = Vec::with_capacity(self.allocated.len());
+        let mut freed = Vec::new();
+        for &(abase, asize) in &self.allocated {
+            let aend = abase + asize;
+            if aend <= base || abase >= end {
+                remaining.push((abase, asize));
+                continue;
+            }
+            if abase < base {
+                remaining.push((abase, base - abase));
+            }
+            if aend > end {
+                remaining.push((end, aend - end));
+            }
+            freed.push((abase.max(base), aend.min(end) - abase.max(base)));
+        }
+        self.allocated = remaining;
+        for (fbase, fsize) in freed {
+            self.insert_free(fbase, fsize);
+        }
+    }
+
+    fn allocate_best_fit(&mut self, size: u64) -> Option<u64> {
+        let idx = self.free.iter()
+            .enumerate()
+            .filter(|&(_, &(_, free_size))| free_size >= size)
+            .min_by_key(|&(_, &(_, free_size))| free_size)
+            .map(|(idx, _)| idx)?;
+        let base = self.free[idx].0;
+        self.carve_free(base, size);
+        self.mark_allocated(base, size);
+        Some(base)
+    }
+
+    /// Pick a uniformly-random page-aligned base in the window and retry on
+    /// collision, falling back to a deterministic best fit if it can't find
+    /// a free spot after a bounded number of attempts.
+    fn allocate_randomized(&mut self, size: u64) -> Option<u64> {
+        let pages = (self.window_end - self.window_base - size) / PAGE_SIZE;
+        if pages > 0 {
+            for _ in 0..64 {
+                let base = self.window_base + self.next_random(pages + 1) * PAGE_SIZE;
+                if self.region_is_free(base, size) {
+                    self.carve_free(base, size);
+                    self.mark_allocated(base, size);
+                    return Some(base);
+                }
+            }
+        }
+        self.allocate_best_fit(size)
+    }
+
+    fn next_random(&mut self, bound: u64) -> u64 {
+        use std::hash::{Hash, Hasher};
+        self.rng_state = self.rng_state.wrapping_add(0x9E3779B97F4A7C15);
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        self.rng_state.hash(&mut hasher);
+        hasher.finish() % bound
+    }
+
+    fn region_is_free(&self, base: u64, size: u64) -> bool {
+        let end = base + size;
+        self.allocated.iter().all(|&(abase, asize)| end <= abase || base >= abase + asize)
+    }
+
+    fn carve_free(&mut self, base: u64, size: u64) {
+        let end = base + size;
+        let mut new_free = Vec::with_capacity(self.free.len() + 1);
+        for &(fbase, fsize) in &self.free {
+            let fend = fbase + fsize;
+            if fend <= base || fbase >= end {
+                new_free.push((fbase, fsize));
+                continue;
+            }
+            if fbase < base {
+                new_free.push((fbase, base - fbase));
+            }
+            if fend > end {
+                new_free.push((end, fend - end));
+            }
         }
+        self.free = new_free;
+    }
+
+    fn insert_free(&mut self, base: u64, size: u64) {
+        self.free.push((base, size));
+        self.free.sort_by_key(|&(base, _)| base);
+        let mut coalesced: Vec<(u64, u64)> = Vec::with_capacity(self.free.len());
+        for &(fbase, fsize) in &self.free {
+            if let Some(last) = coalesced.last_mut() {
+                let last_end = last.0 + last.1;
+                if fbase <= last_end {
+                    last.1 = (fbase + fsize).max(last_end) - last.0;
+                    continue;
+                }
+            }
+            coalesced.push((fbase, fsize));
+        }
+        self.free = coalesced;
+    }
+
+    fn mark_allocated(&mut self, base: u64, size: u64) {
+        self.allocated.push((base, size));
+        self.allocated.sort_by_key(|&(base, _)| base);
+    }
+}
+
+const MAP_FIXED: u32 = 0x10;
+const EINVAL: i64 = 22;
+const ENOMEM: i64 = 12;
+
+/// `mmap`: picks a base through [`RangeAllocator`] (honoring `MAP_FIXED`)
+/// and maps `length` bytes of guest memory there. This replaces the crate's
+/// generic mmap handling rather than wrapping it, so the full `syscall_mmap`
+/// surface (e.g. file-backed mappings) isn't covered here yet.
+fn syscall_mmap<T: Clone>(backend: &Backend<T>, emulator: &AndroidEmulator<T>) {
+    let addr_hint = backend.reg_read(RegisterARM64::X0).unwrap();
+    let length = backend.reg_read(RegisterARM64::X1).unwrap();
+    let prot = backend.reg_read(RegisterARM64::X2).unwrap() as u32;
+    let flags = backend.reg_read(RegisterARM64::X3).unwrap() as u32;
+
+    if length == 0 {
+        backend.reg_write_i64(RegisterARM64::X0, -EINVAL).unwrap();
+        return;
+    }
+
+    let allocator = &mut emulator.inner_mut().range_allocator;
+    let base = if flags & MAP_FIXED != 0 {
+        allocator.allocate_specific(addr_hint, length)
+    } else {
+        allocator.allocate(length)
     };
+
+    match base {
+        Some(base) => {
+            backend.mem_map(base, RangeAllocator::round_up_to_page(length), prot)
+                .expect("failed to map guest memory");
+            backend.reg_write_i64(RegisterARM64::X0, base as i64).unwrap();
+        }
+        None => {
+            backend.reg_write_i64(RegisterARM64::X0, -ENOMEM).unwrap();
+        }
+    }
+}
+
+/// `munmap`: returns the range to [`RangeAllocator`]'s free list (so a later
+/// mmap can reuse it) and unmaps the backing guest memory.
+fn syscall_munmap<T: Clone>(backend: &Backend<T>, emulator: &AndroidEmulator<T>) {
+    let addr = backend.reg_read(RegisterARM64::X0).unwrap();
+    let length = backend.reg_read(RegisterARM64::X1).unwrap();
+
+    if length == 0 {
+        backend.reg_write_i64(RegisterARM64::X0, -EINVAL).unwrap();
+        return;
+    }
+
+    emulator.inner_mut().range_allocator.free(addr, length);
+    let _ = backend.mem_unmap(addr, RangeAllocator::round_up_to_page(length));
+    backend.reg_write_i64(RegisterARM64::X0, 0).unwrap();
 }
 
 pub(crate) fn register_syscall_handler<T: Clone>(emu: &AndroidEmulator<T>) {
@@ -688,4 +1757,234 @@ pub enum Syscalls {
     // __NR_lstat64	 = __NR3264_lstat,
 
     None,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seccomp_data_for(nr: u32) -> SeccompData {
+        SeccompData {
+            nr,
+            arch: AUDIT_ARCH_AARCH64,
+            instruction_pointer: 0,
+            args: [0; 6],
+        }
+    }
+
+    #[test]
+    fn bpf_filter_allows_by_default() {
+        let filter = [SockFilter { code: BPF_RET, jt: 0, jf: 0, k: SECCOMP_RET_ALLOW }];
+        assert_eq!(run_bpf_filter(&filter, &seccomp_data_for(0)), SECCOMP_RET_ALLOW);
+    }
+
+    #[test]
+    fn bpf_filter_matches_syscall_number_via_jeq() {
+        // if (nr == 63) return ERRNO|1; else return ALLOW;
+        let filter = [
+            SockFilter { code: BPF_LD | BPF_ABS, jt: 0, jf: 0, k: 0 },
+            SockFilter { code: BPF_JMP | BPF_JEQ, jt: 0, jf: 1, k: 63 },
+            SockFilter { code: BPF_RET, jt: 0, jf: 0, k: SECCOMP_RET_ERRNO | 1 },
+            SockFilter { code: BPF_RET, jt: 0, jf: 0, k: SECCOMP_RET_ALLOW },
+        ];
+        assert_eq!(run_bpf_filter(&filter, &seccomp_data_for(63)), SECCOMP_RET_ERRNO | 1);
+        assert_eq!(run_bpf_filter(&filter, &seccomp_data_for(64)), SECCOMP_RET_ALLOW);
+    }
+
+    #[test]
+    fn bpf_filter_ja_jumps_over_an_instruction() {
+        // BPF_JA skips the next instruction (k=1), landing on the KILL return.
+        let filter = [
+            SockFilter { code: BPF_JMP | BPF_JA, jt: 0, jf: 0, k: 1 },
+            SockFilter { code: BPF_RET, jt: 0, jf: 0, k: SECCOMP_RET_ALLOW },
+            SockFilter { code: BPF_RET, jt: 0, jf: 0, k: SECCOMP_RET_KILL_THREAD },
+        ];
+        assert_eq!(run_bpf_filter(&filter, &seccomp_data_for(0)), SECCOMP_RET_KILL_THREAD);
+    }
+
+    #[test]
+    fn bpf_filter_falling_off_the_end_kills() {
+        let filter: [SockFilter; 0] = [];
+        assert_eq!(run_bpf_filter(&filter, &seccomp_data_for(0)), SECCOMP_RET_KILL_THREAD);
+    }
+
+    #[test]
+    fn range_allocator_reuses_freed_space() {
+        let mut allocator = RangeAllocator::new(0x1000, 0x10000);
+        let a = allocator.allocate(0x1000).unwrap();
+        allocator.free(a, 0x1000);
+        let b = allocator.allocate(0x1000).unwrap();
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn range_allocator_does_not_hand_out_overlapping_ranges() {
+        let mut allocator = RangeAllocator::new(0x1000, 0x10000);
+        let a = allocator.allocate(0x2000).unwrap();
+        let b = allocator.allocate(0x2000).unwrap();
+        assert!(a + 0x2000 <= b || b + 0x2000 <= a);
+    }
+
+    #[test]
+    fn range_allocator_fixed_evicts_existing_mapping() {
+        let mut allocator = RangeAllocator::new(0x1000, 0x10000);
+        let base = allocator.allocate(0x1000).unwrap();
+        let fixed = allocator.allocate_specific(base, 0x1000).unwrap();
+        assert_eq!(base, fixed);
+    }
+
+    #[test]
+    fn range_allocator_same_seed_replays_the_same_sequence() {
+        let mut a = RangeAllocator::new_seeded(0x1000, 0x100000, 42);
+        let mut b = RangeAllocator::new_seeded(0x1000, 0x100000, 42);
+        a.randomize = true;
+        b.randomize = true;
+        let seq_a: Vec<u64> = (0..8).map(|_| a.allocate(0x1000).unwrap()).collect();
+        let seq_b: Vec<u64> = (0..8).map(|_| b.allocate(0x1000).unwrap()).collect();
+        assert_eq!(seq_a, seq_b);
+    }
+
+    #[test]
+    fn range_allocator_different_seeds_diverge() {
+        let mut a = RangeAllocator::new_seeded(0x1000, 0x100000, 1);
+        let mut b = RangeAllocator::new_seeded(0x1000, 0x100000, 2);
+        a.randomize = true;
+        b.randomize = true;
+        let seq_a: Vec<u64> = (0..8).map(|_| a.allocate(0x1000).unwrap()).collect();
+        let seq_b: Vec<u64> = (0..8).map(|_| b.allocate(0x1000).unwrap()).collect();
+        assert_ne!(seq_a, seq_b);
+    }
+
+    #[test]
+    fn range_allocator_coalesces_adjacent_free_ranges() {
+        let mut allocator = RangeAllocator::new(0x1000, 0x4000);
+        // Window is exactly 3 pages; allocating a 3-page block only succeeds
+        // if freeing the three pages below coalesced them back into one run.
+        let a = allocator.allocate(0x1000).unwrap();
+        let b = allocator.allocate(0x1000).unwrap();
+        let c = allocator.allocate(0x1000).unwrap();
+        allocator.free(a, 0x1000);
+        allocator.free(b, 0x1000);
+        allocator.free(c, 0x1000);
+        assert!(allocator.allocate(0x3000).is_some());
+    }
+
+    #[test]
+    fn cmsg_align_rounds_up_to_8_bytes() {
+        assert_eq!(cmsg_align(0), 0);
+        assert_eq!(cmsg_align(1), 8);
+        assert_eq!(cmsg_align(16), 16);
+        assert_eq!(cmsg_align(17), 24);
+    }
+
+    #[test]
+    fn syscall_tracer_wants_nothing_without_a_sink() {
+        let tracer = SyscallTracer::<()>::default();
+        assert!(!tracer.wants(Syscalls::__NR_openat));
+    }
+
+    #[test]
+    fn syscall_tracer_wants_every_syscall_once_a_sink_is_set_with_no_filter() {
+        let mut tracer = SyscallTracer::<()>::default();
+        tracer.sink = Some(Rc::new(|_, _| {}));
+        assert!(tracer.wants(Syscalls::__NR_openat));
+        assert!(tracer.wants(Syscalls::__NR_close));
+    }
+
+    #[test]
+    fn syscall_tracer_filter_restricts_to_the_named_syscalls() {
+        let mut tracer = SyscallTracer::<()>::default();
+        tracer.sink = Some(Rc::new(|_, _| {}));
+        tracer.filter = Some(["__NR_openat".to_string()].into_iter().collect());
+        assert!(tracer.wants(Syscalls::__NR_openat));
+        assert!(!tracer.wants(Syscalls::__NR_close));
+    }
+
+    #[test]
+    fn format_syscall_ret_formats_the_reserved_errno_range_like_strace() {
+        assert_eq!(format_syscall_ret(0), "0");
+        assert_eq!(format_syscall_ret(3), "3");
+        assert_eq!(format_syscall_ret(-1), "-1 (errno 1)");
+        assert_eq!(format_syscall_ret(-4095), "-1 (errno 4095)");
+        assert_eq!(format_syscall_ret(-4096), "-4096");
+    }
+
+    #[test]
+    fn syscall_registry_defaults_to_the_panic_policy() {
+        let registry = SyscallRegistry::<()>::default();
+        assert_eq!(registry.unsupported_policy, UnsupportedSyscallPolicy::Panic);
+    }
+
+    #[test]
+    fn syscall_registry_get_finds_only_registered_overrides() {
+        let mut registry = SyscallRegistry::<()>::default();
+        assert!(registry.get(Syscalls::__NR_openat).is_none());
+
+        let handler: SyscallOverride<()> = Rc::new(|_, _| {});
+        registry.overrides.insert(Syscalls::__NR_openat as u64, handler);
+
+        assert!(registry.get(Syscalls::__NR_openat).is_some());
+        assert!(registry.get(Syscalls::__NR_close).is_none());
+    }
+
+    #[test]
+    fn parse_scm_rights_bytes_reads_a_single_cmsg() {
+        let buf = write_scm_rights_bytes(&[3, 4, 5]);
+        assert_eq!(parse_scm_rights_bytes(&buf), vec![3, 4, 5]);
+    }
+
+    #[test]
+    fn parse_scm_rights_bytes_ignores_a_truncated_trailing_cmsg() {
+        let mut buf = write_scm_rights_bytes(&[3, 4]);
+        buf.truncate(buf.len() - 1);
+        assert_eq!(parse_scm_rights_bytes(&buf), Vec::<u64>::new());
+    }
+
+    #[test]
+    fn write_scm_rights_bytes_round_trips_through_parse() {
+        let buf = write_scm_rights_bytes(&[]);
+        assert_eq!(parse_scm_rights_bytes(&buf), Vec::<u64>::new());
+        assert_eq!(buf.len() as u64, cmsg_align(16));
+    }
+
+    #[test]
+    fn plan_iovec_copy_fits_entirely_in_one_iovec() {
+        let (takes, truncated) = plan_iovec_copy(&[64], 10);
+        assert_eq!(takes, vec![10]);
+        assert!(!truncated);
+    }
+
+    #[test]
+    fn plan_iovec_copy_splits_across_several_iovecs() {
+        let (takes, truncated) = plan_iovec_copy(&[4, 4, 4], 10);
+        assert_eq!(takes, vec![4, 4, 2]);
+        assert!(!truncated);
+    }
+
+    #[test]
+    fn plan_iovec_copy_reports_truncation_when_capacity_runs_out() {
+        // The regression this guards against: an earlier version reported
+        // the summed iovec capacity (12) as bytes copied even when the
+        // message held only 8. An 8-byte message leaves the third iovec
+        // empty and is not truncated; a 12-byte message into 8 bytes of
+        // capacity copies 8 and is truncated.
+        let (takes, truncated) = plan_iovec_copy(&[4, 4, 4], 8);
+        assert_eq!(takes, vec![4, 4, 0]);
+        assert!(!truncated);
+
+        let (takes, truncated) = plan_iovec_copy(&[4, 4], 12);
+        assert_eq!(takes.iter().sum::<usize>(), 8);
+        assert!(truncated);
+    }
+
+    #[test]
+    fn scm_rights_mint_fd_resolves_back_to_the_original() {
+        let mut state = ScmRightsState::default();
+        let alias_a = state.mint_fd(7);
+        let alias_b = state.mint_fd(9);
+        assert_ne!(alias_a, alias_b);
+        assert_eq!(state.resolve(alias_a), Some(7));
+        assert_eq!(state.resolve(alias_b), Some(9));
+        assert_eq!(state.resolve(alias_a + 1234), None);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,8 @@
+//! Coarse task status used when a syscall needs to halt emulation.
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TaskStatus {
+    /// Execution stopped: an unhandled syscall, an `exit`, or a fatal
+    /// seccomp verdict.
+    X,
+}